@@ -0,0 +1,137 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Formatting of chained method calls and field accesses, e.g.
+// `foo.bar().baz.qux()`.
+
+use visitor::FmtVisitor;
+use utils::make_indent;
+use expr::{last_line_width, wrap_str};
+
+use syntax::{ast, ptr};
+use syntax::codemap::Span;
+use syntax::parse::token;
+use syntax::print::pprust;
+
+// Given the indent style configured for chains, whether a multi-line chain
+// should align each link under the indent column of the root expression, or
+// just use a fixed block indent relative to the current offset.
+#[derive(Copy, Clone)]
+pub enum ChainBaseIndent {
+    Visual,
+    Inherit,
+}
+
+// One link in a method-call/field-access chain, everything except the
+// receiver, which is threaded through separately as the root expression.
+enum ChainItem {
+    Field(ast::Ident, Span),
+    MethodCall(ast::Ident, Vec<ptr::P<ast::Expr>>, Span),
+}
+
+// If `expr` is a link in a method-call/field-access chain, returns the
+// sub-expression it was called on along with a description of the link.
+// Otherwise, returns `expr` unchanged with no link, meaning the chain (or
+// recursion) should stop here.
+fn pop_chain_item(expr: &ast::Expr) -> (&ast::Expr, Option<ChainItem>) {
+    match expr.node {
+        ast::Expr_::ExprMethodCall(ident, _, ref args) => {
+            (&args[0], Some(ChainItem::MethodCall(ident.node, args[1..].to_vec(), expr.span)))
+        }
+        ast::Expr_::ExprField(ref subexpr, ident) => {
+            (subexpr, Some(ChainItem::Field(ident.node, expr.span)))
+        }
+        _ => (expr, None),
+    }
+}
+
+impl<'a> FmtVisitor<'a> {
+    // Formats a chain of method calls and field accesses, e.g.
+    // `foo.bar().baz.qux(1, 2)`. `expr` is the outermost (final) link in the
+    // chain; we walk down to the root receiver, collecting the links along
+    // the way, then render the root followed by each link in order.
+    pub fn rewrite_chain(&mut self, expr: &ast::Expr, width: usize, offset: usize)
+        -> Option<String>
+    {
+        let mut subexpr = expr;
+        let mut links = vec![];
+        loop {
+            let (next, link) = pop_chain_item(subexpr);
+            match link {
+                Some(l) => {
+                    links.push(l);
+                    subexpr = next;
+                }
+                None => break,
+            }
+        }
+        links.reverse();
+
+        let parent = subexpr;
+        let parent_str = try_opt!(self.rewrite_expr(parent, width, offset));
+
+        // Column the chain links are indented to when wrapped onto their own
+        // lines.
+        let indent = match config!(chain_base_indent) {
+            ChainBaseIndent::Visual => offset + last_line_width(&parent_str),
+            ChainBaseIndent::Inherit => offset + self.config.tab_spaces,
+        };
+        // Budget for a link once it's sitting at `indent`, not the budget the
+        // chain as a whole started with.
+        let link_width = try_opt!(config!(max_width).checked_sub(indent));
+
+        let mut link_strs = Vec::with_capacity(links.len());
+        for l in &links {
+            link_strs.push(try_opt!(self.rewrite_chain_item(l, link_width, indent)));
+        }
+
+        // First, try to fit everything on one line.
+        let mut one_line = parent_str.clone();
+        for link in &link_strs {
+            one_line.push_str(link);
+        }
+        if !one_line.contains('\n') && one_line.len() <= width {
+            return Some(one_line);
+        }
+
+        // Doesn't fit (or a link needed to break internally), fall back to
+        // one link per line.
+        let mut result = parent_str;
+        let link_indent = make_indent(indent);
+        for link in &link_strs {
+            result.push('\n');
+            result.push_str(&link_indent);
+            result.push_str(link);
+        }
+        wrap_str(result, config!(max_width), width, offset)
+    }
+
+    fn rewrite_chain_item(&mut self, item: &ChainItem, width: usize, offset: usize)
+        -> Option<String>
+    {
+        match *item {
+            ChainItem::Field(ident, _) => {
+                Some(format!(".{}", token::get_ident(ident)))
+            }
+            ChainItem::MethodCall(ident, ref args, _) => {
+                let name = token::get_ident(ident);
+                // 1 for the leading '.'.
+                let callee_str = format!(".{}", name);
+                let arg_offset = offset + callee_str.len() + 1;
+                let arg_width = try_opt!(width.checked_sub(callee_str.len() + 2));
+                let mut arg_strs = Vec::with_capacity(args.len());
+                for a in args {
+                    arg_strs.push(try_opt!(self.rewrite_expr(a, arg_width, arg_offset)));
+                }
+                Some(format!("{}({})", callee_str, arg_strs.join(", ")))
+            }
+        }
+    }
+}
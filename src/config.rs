@@ -0,0 +1,50 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// All of the formatter's user-facing options, with the defaults we fall
+// back to when a project doesn't override them (e.g. via `rustfmt.toml`).
+
+use lists::SeparatorTactic;
+use chains::ChainBaseIndent;
+use expr::ClosureIndentStyle;
+
+// Reads `field` off whichever `Config` is in scope as `self.config` in the
+// calling method. Kept as a macro (rather than a plain field access) so
+// call sites read the same whether the value ends up coming straight off
+// the struct or, later, from a richer lookup (e.g. per-crate overrides).
+macro_rules! config {
+    ($i: ident) => (self.config.$i)
+}
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    // Maximum width of each line.
+    pub max_width: usize,
+    // Number of spaces per indentation level.
+    pub tab_spaces: usize,
+    // Put a trailing comma after the last field of a struct literal.
+    pub struct_lit_trailing_comma: SeparatorTactic,
+    // How a wrapped method-call/field-access chain indents its links.
+    pub chain_base_indent: ChainBaseIndent,
+    // How a multi-line closure body is indented relative to the `|args|`.
+    pub closure_indent_style: ClosureIndentStyle,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_width: 100,
+            tab_spaces: 4,
+            struct_lit_trailing_comma: SeparatorTactic::Never,
+            chain_base_indent: ChainBaseIndent::Visual,
+            closure_indent_style: ClosureIndentStyle::Visual,
+        }
+    }
+}
@@ -11,82 +11,92 @@
 use visitor::FmtVisitor;
 use utils::*;
 use lists::{write_list, ListFormatting, SeparatorTactic, ListTactic};
+use string::{rewrite_string, StringFormat};
 
 use syntax::{ast, ptr};
-use syntax::codemap::{Pos, Span};
+use syntax::codemap::{BytePos, Pos, Span};
 use syntax::parse::token;
 use syntax::print::pprust;
 
-use MIN_STRING;
+// Given the indent style configured for closures, whether a multi-line body
+// should align under the opening `|`, or just use a fixed block indent
+// relative to the current offset.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ClosureIndentStyle {
+    Visual,
+    Block,
+}
 
-impl<'a> FmtVisitor<'a> {
-    fn rewrite_string_lit(&mut self, s: &str, span: Span, width: usize, offset: usize) -> String {
-        // FIXME I bet this stomps unicode escapes in the source string
+// Macro for giving a Result to the try! macro, but for Option. Returns None
+// from the enclosing function (which must itself return Option<_>) as soon
+// as the wrapped expression evaluates to None, just like try! does for Err.
+macro_rules! try_opt {
+    ($expr:expr) => (match $expr {
+        Some(val) => val,
+        None => return None,
+    })
+}
+
+// Checks that `s` fits in the given budget: the first line must fit in
+// `max_width - offset` (it continues a line that is already `offset` chars
+// in), and every subsequent line must fit in `max_width` on its own. Returns
+// `None` if `s` doesn't fit, so that callers can fall back to another
+// layout instead of emitting an over-long line.
+pub fn wrap_str(s: String, max_width: usize, width: usize, offset: usize) -> Option<String> {
+    let first_line_max_len = ::std::cmp::min(width, max_width.checked_sub(offset).unwrap_or(0));
+
+    let mut lines = s.lines();
+    match lines.next() {
+        Some(first_line) if first_line.len() > first_line_max_len => return None,
+        _ => {}
+    }
+    for line in lines {
+        if line.len() > max_width {
+            return None;
+        }
+    }
 
+    Some(s)
+}
+
+// The number of characters since the last newline in `s` (or the whole
+// length of `s`, if it has none) — i.e. the screen column `s` ends at when
+// written starting at column 0. Used to keep indenting something appended
+// after a multi-line string (e.g. an `else` clause after an `if`'s body)
+// aligned to where that string actually left the cursor, rather than to
+// its total byte length.
+pub fn last_line_width(s: &str) -> usize {
+    match s.rfind('\n') {
+        Some(i) => s.len() - (i + 1),
+        None => s.len(),
+    }
+}
+
+impl<'a> FmtVisitor<'a> {
+    fn rewrite_string_lit(&mut self, s: &str, span: Span, width: usize, offset: usize)
+        -> Option<String>
+    {
         // Check if there is anything to fix: we always try to fixup multi-line
         // strings, or if the string is too long for the line.
         let l_loc = self.codemap.lookup_char_pos(span.lo);
         let r_loc = self.codemap.lookup_char_pos(span.hi);
         if l_loc.line == r_loc.line && r_loc.col.to_usize() <= config!(max_width) {
-            return self.snippet(span);
+            return wrap_str(self.snippet(span), config!(max_width), width, offset);
         }
 
         // TODO if lo.col > IDEAL - 10, start a new line (need cur indent for that)
 
-        let s = s.escape_default();
-
-        let offset = offset + 1;
-        let indent = make_indent(offset);
-        let indent = &indent;
-
-        let mut cur_start = 0;
-        let mut result = String::with_capacity(round_up_to_power_of_two(s.len()));
-        result.push('"');
-        loop {
-            let max_chars = if cur_start == 0 {
-                // First line.
-                width - 2 // 2 = " + \
-            } else {
-                config!(max_width) - offset - 1 // 1 = either \ or ;
-            };
-
-            let mut cur_end = cur_start + max_chars;
-
-            if cur_end >= s.len() {
-                result.push_str(&s[cur_start..]);
-                break;
-            }
-
-            // Make sure we're on a char boundary.
-            cur_end = next_char(&s, cur_end);
-
-            // Push cur_end left until we reach whitespace
-            while !s.char_at(cur_end-1).is_whitespace() {
-                cur_end = prev_char(&s, cur_end);
-
-                if cur_end - cur_start < MIN_STRING {
-                    // We can't break at whitespace, fall back to splitting
-                    // anywhere that doesn't break an escape sequence
-                    cur_end = next_char(&s, cur_start + max_chars);
-                    while s.char_at(prev_char(&s, cur_end)) == '\\' {
-                        cur_end = prev_char(&s, cur_end);
-                    }
-                    break;
-                }
-            }
-            // Make sure there is no whitespace to the right of the break.
-            while cur_end < s.len() && s.char_at(cur_end).is_whitespace() {
-                cur_end = next_char(&s, cur_end+1);
-            }
-            result.push_str(&s[cur_start..cur_end]);
-            result.push_str("\\\n");
-            result.push_str(indent);
-
-            cur_start = cur_end;
-        }
-        result.push('"');
+        let fmt = StringFormat {
+            opener: "\"",
+            closer: "\"",
+            line_end: "\\",
+            offset: offset + 1,
+            width: width,
+            max_width: config!(max_width),
+            trim_end: true,
+        };
 
-        result
+        rewrite_string(&s.escape_default(), &fmt)
     }
 
     fn rewrite_call(&mut self,
@@ -94,22 +104,24 @@ impl<'a> FmtVisitor<'a> {
                     args: &[ptr::P<ast::Expr>],
                     width: usize,
                     offset: usize)
-        -> String
+        -> Option<String>
     {
         debug!("rewrite_call, width: {}, offset: {}", width, offset);
 
         // TODO using byte lens instead of char lens (and probably all over the place too)
-        let callee_str = self.rewrite_expr(callee, width, offset);
+        let callee_str = try_opt!(self.rewrite_expr(callee, width, offset));
         debug!("rewrite_call, callee_str: `{}`", callee_str);
         // 2 is for parens.
-        let remaining_width = width - callee_str.len() - 2;
+        let remaining_width = try_opt!(width.checked_sub(callee_str.len() + 2));
         let offset = callee_str.len() + 1 + offset;
         let arg_count = args.len();
 
         let args_str = if arg_count > 0 {
-            let args: Vec<_> = args.iter().map(|e| (self.rewrite_expr(e,
-                                                                      remaining_width,
-                                                                      offset), String::new())).collect();
+            let mut args_strs = Vec::with_capacity(arg_count);
+            for e in args {
+                let arg_str = try_opt!(self.rewrite_expr(e, remaining_width, offset));
+                args_strs.push((arg_str, String::new()));
+            }
             let fmt = ListFormatting {
                 tactic: ListTactic::HorizontalVertical,
                 separator: ",",
@@ -118,21 +130,26 @@ impl<'a> FmtVisitor<'a> {
                 h_width: remaining_width,
                 v_width: remaining_width,
             };
-            write_list(&args, &fmt)
+            // `write_list` tries a horizontal layout first and falls back to
+            // one argument per line, indented under the opening paren, if
+            // that doesn't fit.
+            try_opt!(write_list(&args_strs, &fmt))
         } else {
             String::new()
         };
 
-        format!("{}({})", callee_str, args_str)
+        Some(format!("{}({})", callee_str, args_str))
     }
 
-    fn rewrite_paren(&mut self, subexpr: &ast::Expr, width: usize, offset: usize) -> String {
+    fn rewrite_paren(&mut self, subexpr: &ast::Expr, width: usize, offset: usize)
+        -> Option<String>
+    {
         debug!("rewrite_paren, width: {}, offset: {}", width, offset);
         // 1 is for opening paren, 2 is for opening+closing, we want to keep the closing
         // paren on the same line as the subexpr
-        let subexpr_str = self.rewrite_expr(subexpr, width-2, offset+1);
+        let subexpr_str = try_opt!(self.rewrite_expr(subexpr, try_opt!(width.checked_sub(2)), offset+1));
         debug!("rewrite_paren, subexpr_str: `{}`", subexpr_str);
-        format!("({})", subexpr_str)
+        Some(format!("({})", subexpr_str))
     }
 
     fn rewrite_struct_lit(&mut self,
@@ -141,7 +158,7 @@ impl<'a> FmtVisitor<'a> {
                           base: Option<&ast::Expr>,
                           width: usize,
                           offset: usize)
-        -> String
+        -> Option<String>
     {
         debug!("rewrite_struct_lit: width {}, offset {}", width, offset);
         assert!(fields.len() > 0 || base.is_some());
@@ -149,13 +166,17 @@ impl<'a> FmtVisitor<'a> {
         let path_str = pprust::path_to_string(path);
         // Foo { a: Foo } - indent is +3, width is -5.
         let indent = offset + path_str.len() + 3;
-        let budget = width - (path_str.len() + 5);
+        let budget = try_opt!(width.checked_sub(path_str.len() + 5));
 
-        let mut field_strs: Vec<_> =
-            fields.iter().map(|f| self.rewrite_field(f, budget, indent)).collect();
+        let mut field_strs = Vec::with_capacity(fields.len());
+        for f in fields {
+            field_strs.push(try_opt!(self.rewrite_field(f, budget, indent)));
+        }
         if let Some(expr) = base {
             // Another 2 on the width/indent for the ..
-            field_strs.push(format!("..{}", self.rewrite_expr(expr, budget - 2, indent + 2)))
+            let base_budget = try_opt!(budget.checked_sub(2));
+            let base_str = try_opt!(self.rewrite_expr(expr, base_budget, indent + 2));
+            field_strs.push(format!("..{}", base_str))
         }
 
         // FIXME comments
@@ -172,41 +193,58 @@ impl<'a> FmtVisitor<'a> {
             h_width: budget,
             v_width: budget,
         };
-        let fields_str = write_list(&field_strs, &fmt);
-        format!("{} {{ {} }}", path_str, fields_str)
+        // `write_list` tries a horizontal layout first and falls back to one
+        // field per line, indented under `indent`, if that doesn't fit.
+        let fields_str = try_opt!(write_list(&field_strs, &fmt));
 
-        // FIXME if the usual multi-line layout is too wide, we should fall back to
-        // Foo {
-        //     a: ...,
-        // }
+        if fields_str.contains('\n') {
+            // Vertical tactic kicked in: lay the braces out on their own
+            // lines too, rather than wrapping a multi-line field list in a
+            // single-line `Foo { ... }`.
+            // Foo {
+            //     a: ...,
+            //     b: ...,
+            // }
+            let result = format!("{} {{\n{}\n{}}}", path_str, fields_str, make_indent(offset));
+            wrap_str(result, config!(max_width), width, offset)
+        } else {
+            let one_line = format!("{} {{ {} }}", path_str, fields_str);
+            wrap_str(one_line, config!(max_width), width, offset)
+        }
     }
 
-    fn rewrite_field(&mut self, field: &ast::Field, width: usize, offset: usize) -> String {
+    fn rewrite_field(&mut self, field: &ast::Field, width: usize, offset: usize)
+        -> Option<String>
+    {
         let name = &token::get_ident(field.ident.node);
         let overhead = name.len() + 2;
-        let expr = self.rewrite_expr(&field.expr, width - overhead, offset + overhead);
-        format!("{}: {}", name, expr)
+        let width = try_opt!(width.checked_sub(overhead));
+        let expr = try_opt!(self.rewrite_expr(&field.expr, width, offset + overhead));
+        Some(format!("{}: {}", name, expr))
     }
 
     fn rewrite_tuple_lit(&mut self, items: &[ptr::P<ast::Expr>], width: usize, offset: usize)
-        -> String {
+        -> Option<String>
+    {
         // opening paren
         let indent = offset + 1;
         // In case of length 1, need a trailing comma
         if items.len() == 1 {
-            return format!("({},)", self.rewrite_expr(&*items[0], width - 3, indent));
+            let item_str = try_opt!(self.rewrite_expr(&*items[0], try_opt!(width.checked_sub(3)), indent));
+            return Some(format!("({},)", item_str));
         }
         // Only last line has width-1 as budget, other may take max_width
-        let item_strs: Vec<_> =
-            items.iter()
-                 .enumerate()
-                 .map(|(i, item)| self.rewrite_expr(
-                    item,
-                    // last line : given width (minus "("+")"), other lines : max_width
-                    // (minus "("+","))
-                    if i == items.len() - 1 { width - 2 } else { config!(max_width) - indent - 2 },
-                    indent))
-                 .collect();
+        let mut item_strs = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            // last line : given width (minus "("+")"), other lines : max_width
+            // (minus "("+","))
+            let item_width = if i == items.len() - 1 {
+                try_opt!(width.checked_sub(2))
+            } else {
+                try_opt!(config!(max_width).checked_sub(indent + 2))
+            };
+            item_strs.push(try_opt!(self.rewrite_expr(item, item_width, indent)));
+        }
         let tactics = if item_strs.iter().any(|s| s.contains('\n')) {
             ListTactic::Vertical
         } else {
@@ -214,29 +252,266 @@ impl<'a> FmtVisitor<'a> {
         };
         // FIXME handle comments
         let item_strs: Vec<_> = item_strs.into_iter().map(|s| (s, String::new())).collect();
+        let list_width = try_opt!(width.checked_sub(2));
         let fmt = ListFormatting {
             tactic: tactics,
             separator: ",",
             trailing_separator: SeparatorTactic::Never,
             indent: indent,
-            h_width: width - 2,
-            v_width: width - 2,
+            h_width: list_width,
+            v_width: list_width,
+        };
+        let item_str = try_opt!(write_list(&item_strs, &fmt));
+        Some(format!("({})", item_str))
+    }
+
+    fn rewrite_closure(&mut self,
+                       capture: ast::CaptureClause,
+                       fn_decl: &ast::FnDecl,
+                       body: &ast::Block,
+                       width: usize,
+                       offset: usize)
+        -> Option<String>
+    {
+        let mover = if capture == ast::CaptureClause::CaptureByValue { "move " } else { "" };
+
+        let arg_strs: Vec<_> = fn_decl.inputs.iter().map(|a| self.rewrite_closure_arg(a)).collect();
+        // An explicit `-> T` forces a block body: Rust doesn't allow the
+        // single-expression form (`|x| -> T x`) to appear without braces.
+        let ret_str = match fn_decl.output {
+            ast::FunctionRetTy::Return(ref ty) => format!("-> {} ", pprust::ty_to_string(ty)),
+            _ => String::new(),
+        };
+        let prefix = format!("{}|{}| {}", mover, arg_strs.join(", "), ret_str);
+
+        // A closure with no statements, a single tail expression, and no
+        // annotated return type can stay on one line, e.g. `|x| x + 1`.
+        if ret_str.is_empty() && body.stmts.is_empty() {
+            if let Some(ref expr) = body.expr {
+                if let Some(body_budget) = width.checked_sub(prefix.len()) {
+                    let body_offset = offset + prefix.len();
+                    if let Some(body_str) = self.rewrite_expr(expr, body_budget, body_offset) {
+                        if !body_str.contains('\n') {
+                            return Some(format!("{}{}", prefix, body_str));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Otherwise, expand the body into a braced block on following lines.
+        let block_indent = match config!(closure_indent_style) {
+            ClosureIndentStyle::Visual => offset + prefix.len(),
+            ClosureIndentStyle::Block => offset + self.config.tab_spaces,
+        };
+        let block_str = try_opt!(self.rewrite_block(body, block_indent, offset));
+        Some(format!("{}{}", prefix, block_str))
+    }
+
+    fn rewrite_closure_arg(&mut self, arg: &ast::Arg) -> String {
+        let pat_str = pprust::pat_to_string(&arg.pat);
+        match arg.ty.node {
+            ast::Ty_::TyInfer => pat_str,
+            _ => format!("{}: {}", pat_str, pprust::ty_to_string(&arg.ty)),
+        }
+    }
+
+    // The text between `lo` and `hi` (e.g. the gap between a condition and
+    // the opening brace of its block), trimmed and re-wrapped in single
+    // spaces. `model_span` lends its expansion context, since `lo`/`hi` on
+    // their own aren't a full `Span`. A gap that's pure whitespace collapses
+    // to one separating space; anything else (a comment) is kept, so it
+    // isn't silently dropped on reformat.
+    fn rewrite_gap(&self, lo: BytePos, hi: BytePos, model_span: Span) -> String {
+        let gap = self.snippet(Span { lo: lo, hi: hi, ..model_span });
+        let gap = gap.trim();
+        if gap.is_empty() {
+            " ".to_string()
+        } else {
+            format!(" {} ", gap)
+        }
+    }
+
+    // Renders `block`'s statements at `inner_indent`, one per line, with the
+    // closing brace back at `close_offset`. Shared by closures and the
+    // block-bearing control-flow expressions below.
+    fn rewrite_block(&mut self, block: &ast::Block, inner_indent: usize, close_offset: usize)
+        -> Option<String>
+    {
+        let indent_str = make_indent(inner_indent);
+        let mut result = String::from("{\n");
+        for stmt in &block.stmts {
+            let stmt_str = try_opt!(self.rewrite_stmt(stmt, inner_indent));
+            result.push_str(&indent_str);
+            result.push_str(&stmt_str);
+            result.push('\n');
+        }
+        if let Some(ref expr) = block.expr {
+            let expr_budget = try_opt!(config!(max_width).checked_sub(inner_indent));
+            let expr_str = try_opt!(self.rewrite_expr(expr, expr_budget, inner_indent));
+            result.push_str(&indent_str);
+            result.push_str(&expr_str);
+            result.push('\n');
+        }
+        result.push_str(&make_indent(close_offset));
+        result.push('}');
+        Some(result)
+    }
+
+    // Rewrites a single statement at `offset`, the column its first line
+    // starts at. Expression statements are run back through `rewrite_expr`
+    // (so the chain/call/struct-lit/closure combinators above apply inside
+    // function, loop, if and closure bodies, not just at the top level);
+    // `let` bindings get their initializer rewritten the same way. Anything
+    // else (item or macro statements) falls back to the snippet, same as
+    // `rewrite_expr` does for expression kinds it doesn't understand yet.
+    fn rewrite_stmt(&mut self, stmt: &ast::Stmt, offset: usize) -> Option<String> {
+        match stmt.node {
+            ast::Stmt_::StmtExpr(ref expr, _) => {
+                let width = try_opt!(config!(max_width).checked_sub(offset));
+                self.rewrite_expr(expr, width, offset)
+            }
+            ast::Stmt_::StmtSemi(ref expr, _) => {
+                let width = try_opt!(config!(max_width).checked_sub(offset + 1));
+                let expr_str = try_opt!(self.rewrite_expr(expr, width, offset));
+                Some(format!("{};", expr_str))
+            }
+            ast::Stmt_::StmtDecl(ref decl, _) => {
+                match decl.node {
+                    ast::Decl_::DeclLocal(ref local) => self.rewrite_let(local, offset),
+                    ast::Decl_::DeclItem(_) => Some(self.snippet(stmt.span)),
+                }
+            }
+            ast::Stmt_::StmtMac(..) => Some(self.snippet(stmt.span)),
+        }
+    }
+
+    fn rewrite_let(&mut self, local: &ast::Local, offset: usize) -> Option<String> {
+        let pat_str = pprust::pat_to_string(&local.pat);
+        let ty_str = match local.ty {
+            Some(ref ty) => format!(": {}", pprust::ty_to_string(ty)),
+            None => String::new(),
+        };
+        let prefix = format!("let {}{}", pat_str, ty_str);
+
+        match local.init {
+            Some(ref init) => {
+                let eq_str = " = ";
+                let init_offset = offset + prefix.len() + eq_str.len();
+                // 1 for the trailing `;`.
+                let init_width = try_opt!(config!(max_width).checked_sub(init_offset + 1));
+                let init_str = try_opt!(self.rewrite_expr(init, init_width, init_offset));
+                Some(format!("{}{}{};", prefix, eq_str, init_str))
+            }
+            None => Some(format!("{};", prefix)),
+        }
+    }
+
+    fn rewrite_loop(&mut self,
+                    span: Span,
+                    block: &ast::Block,
+                    label: Option<ast::Ident>,
+                    width: usize,
+                    offset: usize)
+        -> Option<String>
+    {
+        let label_str = match label {
+            Some(ident) => format!("{}: ", token::get_ident(ident)),
+            None => String::new(),
+        };
+        // `span` only bounds the whole `loop { ... }` (or `'a: loop { ... }`)
+        // expression, and a label's own span isn't tracked separately, so
+        // there's no reliable byte offset for where `loop` itself starts
+        // when a label is present. Only attempt the gap snippet when there
+        // isn't one; a labelled loop falls back to a single space, the same
+        // as an unlabelled loop with nothing but whitespace in the gap.
+        let gap = if label.is_none() {
+            let keyword_end = BytePos::from_usize(span.lo.to_usize() + "loop".len());
+            self.rewrite_gap(keyword_end, block.span.lo, span)
+        } else {
+            " ".to_string()
+        };
+        let block_str = try_opt!(self.rewrite_block(block, offset + self.config.tab_spaces, offset));
+        wrap_str(format!("{}loop{}{}", label_str, gap, block_str), config!(max_width), width, offset)
+    }
+
+    fn rewrite_while(&mut self,
+                     cond: &ast::Expr,
+                     block: &ast::Block,
+                     label: Option<ast::Ident>,
+                     width: usize,
+                     offset: usize)
+        -> Option<String>
+    {
+        let label_str = match label {
+            Some(ident) => format!("{}: ", token::get_ident(ident)),
+            None => String::new(),
         };
-        let item_str = write_list(&item_strs, &fmt);
-        format!("({})", item_str)
+        let prefix = format!("{}while ", label_str);
+        let cond_budget = try_opt!(width.checked_sub(prefix.len() + 2));
+        let cond_str = try_opt!(self.rewrite_expr(cond, cond_budget, offset + prefix.len()));
+        let gap = self.rewrite_gap(cond.span.hi, block.span.lo, cond.span);
+        let block_str = try_opt!(self.rewrite_block(block, offset + self.config.tab_spaces, offset));
+        wrap_str(format!("{}{}{}{}", prefix, cond_str, gap, block_str), config!(max_width), width, offset)
     }
 
+    fn rewrite_if(&mut self,
+                 cond: &ast::Expr,
+                 then: &ast::Block,
+                 else_opt: Option<&ast::Expr>,
+                 width: usize,
+                 offset: usize)
+        -> Option<String>
+    {
+        let prefix = "if ";
+        let cond_budget = try_opt!(width.checked_sub(prefix.len() + 2));
+        let cond_str = try_opt!(self.rewrite_expr(cond, cond_budget, offset + prefix.len()));
+        let gap = self.rewrite_gap(cond.span.hi, then.span.lo, cond.span);
+        let then_str = try_opt!(self.rewrite_block(then, offset + self.config.tab_spaces, offset));
+        let mut result = format!("{}{}{}{}", prefix, cond_str, gap, then_str);
+
+        if let Some(else_expr) = else_opt {
+            result.push_str(" else ");
+            // The column `else` actually ends up at on the page, not the
+            // byte length of the (possibly multi-line) `result` so far.
+            let else_offset = last_line_width(&result);
+            let else_width = try_opt!(config!(max_width).checked_sub(else_offset));
+            match else_expr.node {
+                // `else if` recurses so that `if`/`else if`/`else` chains of
+                // any length are handled.
+                ast::Expr_::ExprIf(ref else_cond, ref else_then, ref else_else) => {
+                    let else_str = try_opt!(self.rewrite_if(else_cond,
+                                                            else_then,
+                                                            else_else.as_ref().map(|e| &**e),
+                                                            else_width,
+                                                            else_offset));
+                    result.push_str(&else_str);
+                }
+                ast::Expr_::ExprBlock(ref else_block) => {
+                    let else_str = try_opt!(self.rewrite_block(else_block,
+                                                               offset + self.config.tab_spaces,
+                                                               offset));
+                    result.push_str(&else_str);
+                }
+                _ => return None,
+            }
+        }
 
-    pub fn rewrite_expr(&mut self, expr: &ast::Expr, width: usize, offset: usize) -> String {
-        match expr.node {
+        wrap_str(result, config!(max_width), width, offset)
+    }
+
+    pub fn rewrite_expr(&mut self, expr: &ast::Expr, width: usize, offset: usize)
+        -> Option<String>
+    {
+        let result = match expr.node {
             ast::Expr_::ExprLit(ref l) => {
                 match l.node {
                     ast::Lit_::LitStr(ref is, _) => {
                         let result = self.rewrite_string_lit(&is, l.span, width, offset);
-                        debug!("string lit: `{}`", result);
+                        debug!("string lit: `{:?}`", result);
                         return result;
                     }
-                    _ => {}
+                    _ => None,
                 }
             }
             ast::Expr_::ExprCall(ref callee, ref args) => {
@@ -255,9 +530,61 @@ impl<'a> FmtVisitor<'a> {
             ast::Expr_::ExprTup(ref items) => {
                 return self.rewrite_tuple_lit(items, width, offset);
             }
-            _ => {}
-        }
+            ast::Expr_::ExprMethodCall(..) | ast::Expr_::ExprField(..) => {
+                return self.rewrite_chain(expr, width, offset);
+            }
+            ast::Expr_::ExprClosure(capture, ref fn_decl, ref body, _) => {
+                return self.rewrite_closure(capture, fn_decl, body, width, offset);
+            }
+            ast::Expr_::ExprBlock(ref block) => {
+                return self.rewrite_block(block, offset + self.config.tab_spaces, offset);
+            }
+            ast::Expr_::ExprLoop(ref block, label) => {
+                return self.rewrite_loop(expr.span, block, label, width, offset);
+            }
+            ast::Expr_::ExprWhile(ref cond, ref block, label) => {
+                return self.rewrite_while(cond, block, label, width, offset);
+            }
+            ast::Expr_::ExprIf(ref cond, ref then, ref else_opt) => {
+                return self.rewrite_if(cond, then, else_opt.as_ref().map(|e| &**e), width, offset);
+            }
+            _ => None,
+        };
+
+        result.or_else(|| wrap_str(self.snippet(expr.span), config!(max_width), width, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{last_line_width, wrap_str};
+
+    #[test]
+    fn last_line_width_single_line() {
+        assert_eq!(last_line_width("if foo {"), 8);
+    }
+
+    #[test]
+    fn last_line_width_after_newline() {
+        assert_eq!(last_line_width("if foo {\n    bar();\n} "), 1);
+    }
+
+    #[test]
+    fn wrap_str_fits_on_first_line() {
+        let s = "foo(1, 2)".to_string();
+        assert_eq!(wrap_str(s.clone(), 100, 20, 4), Some(s));
+    }
+
+    #[test]
+    fn wrap_str_first_line_too_long() {
+        let s = "foo(1, 2)".to_string();
+        // Budget for the first line is min(width, max_width - offset) = 5.
+        assert_eq!(wrap_str(s, 100, 20, 95), None);
+    }
 
-        self.snippet(expr.span)
+    #[test]
+    fn wrap_str_later_line_too_long() {
+        let s = "foo(\n    a_very_long_line_that_does_not_fit_in_max_width)".to_string();
+        assert_eq!(wrap_str(s, 10, 20, 0), None);
     }
 }
@@ -0,0 +1,185 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Shared layout for comma-separated lists (call arguments, struct literal
+// fields, tuple elements, ...): try everything on one line, and fall back
+// to one item per line when it doesn't fit.
+
+use utils::make_indent;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SeparatorTactic {
+    Always,
+    Never,
+}
+
+#[derive(Copy, Clone)]
+pub enum ListTactic {
+    // Force a single line, fail (return `None`) if it doesn't fit.
+    Horizontal,
+    // Force one item per line.
+    Vertical,
+    // Try `Horizontal` first, fall back to `Vertical` if it doesn't fit.
+    HorizontalVertical,
+}
+
+pub struct ListFormatting<'a> {
+    pub tactic: ListTactic,
+    pub separator: &'a str,
+    pub trailing_separator: SeparatorTactic,
+    // Column items are indented to when laid out one per line.
+    pub indent: usize,
+    // Budget available for the horizontal (one-line) layout.
+    pub h_width: usize,
+    // Budget available for each line of the vertical layout.
+    pub v_width: usize,
+}
+
+// Lays out `items` (each item paired with its trailing comment, currently
+// unused pending proper comment support) according to `fmt`. Returns `None`
+// if the items don't fit their tactic's budget: the whole joined line
+// against `h_width` for `Horizontal`, or any individual item against
+// `v_width` for `Vertical`.
+pub fn write_list(items: &[(String, String)], fmt: &ListFormatting) -> Option<String> {
+    if items.is_empty() {
+        return Some(String::new());
+    }
+
+    match fmt.tactic {
+        ListTactic::Horizontal => write_list_horizontal(items, fmt),
+        ListTactic::Vertical => write_list_vertical(items, fmt),
+        ListTactic::HorizontalVertical => {
+            write_list_horizontal(items, fmt).or_else(|| write_list_vertical(items, fmt))
+        }
+    }
+}
+
+fn write_list_horizontal(items: &[(String, String)], fmt: &ListFormatting) -> Option<String> {
+    let mut result = String::new();
+    for (i, &(ref item, _)) in items.iter().enumerate() {
+        if item.contains('\n') {
+            // A multi-line item can never be part of a horizontal layout.
+            return None;
+        }
+        if i > 0 {
+            result.push_str(fmt.separator);
+            result.push(' ');
+        }
+        result.push_str(item);
+    }
+    if let SeparatorTactic::Always = fmt.trailing_separator {
+        result.push_str(fmt.separator);
+    }
+
+    if result.len() > fmt.h_width {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+fn write_list_vertical(items: &[(String, String)], fmt: &ListFormatting) -> Option<String> {
+    let indent_str = make_indent(fmt.indent);
+    let last = items.len() - 1;
+
+    let mut result = String::new();
+    for (i, &(ref item, _)) in items.iter().enumerate() {
+        let trailing_separator = i != last || fmt.trailing_separator == SeparatorTactic::Always;
+
+        // Check each of the item's lines against v_width: the first carries
+        // the indent, the last the trailing separator (if any).
+        let lines: Vec<&str> = item.lines().collect();
+        for (j, line) in lines.iter().enumerate() {
+            let mut len = line.len();
+            if j == 0 {
+                len += fmt.indent;
+            }
+            if j == lines.len() - 1 && trailing_separator {
+                len += fmt.separator.len();
+            }
+            if len > fmt.v_width {
+                return None;
+            }
+        }
+
+        result.push_str(&indent_str);
+        result.push_str(item);
+        if trailing_separator {
+            result.push_str(fmt.separator);
+        }
+        if i != last {
+            result.push('\n');
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(strs: &[&str]) -> Vec<(String, String)> {
+        strs.iter().map(|s| (s.to_string(), String::new())).collect()
+    }
+
+    #[test]
+    fn horizontal_fits_stays_on_one_line() {
+        let fmt = ListFormatting {
+            tactic: ListTactic::HorizontalVertical,
+            separator: ",",
+            trailing_separator: SeparatorTactic::Never,
+            indent: 4,
+            h_width: 80,
+            v_width: 80,
+        };
+        let result = write_list(&items(&["a", "b", "c"]), &fmt);
+        assert_eq!(result, Some("a, b, c".to_string()));
+    }
+
+    #[test]
+    fn horizontal_overflow_falls_back_to_vertical() {
+        let fmt = ListFormatting {
+            tactic: ListTactic::HorizontalVertical,
+            separator: ",",
+            trailing_separator: SeparatorTactic::Always,
+            indent: 4,
+            h_width: 5,
+            v_width: 80,
+        };
+        let result = write_list(&items(&["a", "b", "c"]), &fmt).unwrap();
+        assert_eq!(result, "    a,\n    b,\n    c,");
+    }
+
+    #[test]
+    fn horizontal_only_fails_when_it_does_not_fit() {
+        let fmt = ListFormatting {
+            tactic: ListTactic::Horizontal,
+            separator: ",",
+            trailing_separator: SeparatorTactic::Never,
+            indent: 4,
+            h_width: 5,
+            v_width: 80,
+        };
+        assert_eq!(write_list(&items(&["a", "b", "c"]), &fmt), None);
+    }
+
+    #[test]
+    fn vertical_fails_when_an_item_overflows_v_width() {
+        let fmt = ListFormatting {
+            tactic: ListTactic::Vertical,
+            separator: ",",
+            trailing_separator: SeparatorTactic::Never,
+            indent: 4,
+            h_width: 80,
+            v_width: 5,
+        };
+        assert_eq!(write_list(&items(&["a", "way_too_long_for_v_width", "c"]), &fmt), None);
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright 2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Escape-aware wrapping of string-like literals (string literals, doc
+// comments, ...) across multiple lines.
+
+use std::cmp;
+
+use utils::make_indent;
+
+use MIN_STRING;
+
+// The knobs that differ between callers of `rewrite_string`: a string
+// literal wants a leading/trailing `"` and a `\` line continuation, while a
+// doc comment wants a `///` prefix on each line and no continuation marker
+// at all.
+pub struct StringFormat<'a> {
+    // Text put before the first token, e.g. `"`.
+    pub opener: &'a str,
+    // Text put after the last token, e.g. `"`.
+    pub closer: &'a str,
+    // Text appended to every line except the last before the newline, e.g.
+    // `\` for a string literal's line continuation.
+    pub line_end: &'a str,
+    // Column each line after the first is indented to.
+    pub offset: usize,
+    // Maximum width of the first line; subsequent lines are bound by
+    // `max_width` instead, same as everywhere else in the formatter.
+    pub width: usize,
+    // The formatter's configured max line width, passed in because this is a
+    // free function with no `self.config` to read it from.
+    pub max_width: usize,
+    // Whether to trim trailing whitespace from a line before appending
+    // `line_end`, so a continuation backslash never ends up preceded by
+    // whitespace that would otherwise become part of the decoded string.
+    pub trim_end: bool,
+}
+
+// Splits an already-escaped (so, ASCII-only) string into its atomic
+// tokens: every escape sequence (`\n`, `\t`, `\\`, `\u{1f600}`, ...) is one
+// token, and every other byte is its own token. `rewrite_string` only ever
+// breaks a line between tokens, so a wrap can land right after `\u{1f600}`
+// but never inside it.
+fn tokenize(s: &str) -> Vec<&str> {
+    let bytes = s.as_bytes();
+    let mut tokens = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 1 < bytes.len() {
+            let mut end = i + 2;
+            if bytes[i + 1] == b'u' && end < bytes.len() && bytes[end] == b'{' {
+                // \u{...} can be several digits long; consume up to (and
+                // including) the closing brace as a single token.
+                while end < bytes.len() && bytes[end] != b'}' {
+                    end += 1;
+                }
+                if end < bytes.len() {
+                    end += 1;
+                }
+            }
+            tokens.push(&s[i..end]);
+            i = end;
+        } else {
+            tokens.push(&s[i..i + 1]);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+// Greedily fills lines of `fmt.width` (`MAX_WIDTH` after the first line)
+// with the tokens of `s` (see `tokenize`), preferring to break at a
+// whitespace token and never breaking in the middle of an escape sequence.
+// The result round-trips: concatenating the lines (dropping
+// `fmt.opener`/`fmt.closer`/`fmt.line_end` and the indent) yields `s` back
+// unchanged.
+pub fn rewrite_string(s: &str, fmt: &StringFormat) -> Option<String> {
+    let tokens = tokenize(s);
+
+    let indent = make_indent(fmt.offset);
+    let mut result = String::with_capacity(s.len() + fmt.opener.len() + fmt.closer.len());
+    result.push_str(fmt.opener);
+
+    let mut cur_start = 0;
+    let mut first_line = true;
+    while cur_start < tokens.len() {
+        let budget = if first_line {
+            try_opt!(fmt.width.checked_sub(fmt.opener.len() + fmt.line_end.len()))
+        } else {
+            try_opt!(fmt.max_width.checked_sub(fmt.offset + fmt.line_end.len()))
+        };
+
+        let mut cur_end = fit_tokens(&tokens, cur_start, budget);
+
+        if cur_end < tokens.len() {
+            // Prefer to break at a whitespace token, but don't give up so
+            // much of the line that we'd undershoot MIN_STRING worth of
+            // content.
+            let mut break_at = cur_end;
+            while break_at > cur_start + MIN_STRING && !is_whitespace(tokens[break_at - 1]) {
+                break_at -= 1;
+            }
+            if is_whitespace(tokens[break_at - 1]) {
+                cur_end = break_at;
+            }
+            // Otherwise there's no whitespace to break at within budget;
+            // `cur_end` (a token boundary, so never mid-escape) is used
+            // as-is.
+        }
+
+        let mut line = tokens[cur_start..cur_end].concat();
+        if fmt.trim_end {
+            let trimmed_len = line.trim_right().len();
+            line.truncate(trimmed_len);
+        }
+
+        if !first_line {
+            result.push_str(&indent);
+        }
+        result.push_str(&line);
+
+        // Skip whitespace tokens immediately following the break so we
+        // don't echo them back at the start of the next line.
+        cur_start = cur_end;
+        while cur_start < tokens.len() && is_whitespace(tokens[cur_start]) {
+            cur_start += 1;
+        }
+
+        if cur_start < tokens.len() {
+            result.push_str(fmt.line_end);
+            result.push('\n');
+        }
+
+        first_line = false;
+    }
+
+    result.push_str(fmt.closer);
+    Some(result)
+}
+
+// The furthest token index from `start` whose tokens' total length still
+// fits in `budget` bytes. Always at least `start` (i.e. can return a chunk
+// of zero tokens if even the first doesn't fit, to avoid looping forever).
+fn fit_tokens(tokens: &[&str], start: usize, budget: usize) -> usize {
+    let mut used = 0;
+    let mut end = start;
+    while end < tokens.len() {
+        let next_used = used + tokens[end].len();
+        if next_used > budget {
+            break;
+        }
+        used = next_used;
+        end += 1;
+    }
+    cmp::max(end, start)
+}
+
+fn is_whitespace(token: &str) -> bool {
+    token.len() == 1 && token.chars().next().map_or(false, |c| c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rewrite_string, tokenize, StringFormat};
+
+    #[test]
+    fn tokenize_keeps_unicode_escape_whole() {
+        let tokens = tokenize(r"a\u{1f600}b");
+        assert_eq!(tokens, vec!["a", r"\u{1f600}", "b"]);
+    }
+
+    #[test]
+    fn tokenize_keeps_simple_escapes_whole() {
+        let tokens = tokenize(r"a\nb\\c");
+        assert_eq!(tokens, vec!["a", r"\n", "b", r"\\", "c"]);
+    }
+
+    #[test]
+    fn rewrite_string_never_splits_mid_escape() {
+        // Force a wrap right where a naive byte-based splitter would land
+        // inside the `\u{1f600}` escape.
+        let s = format!("word {}word", r"\u{1f600}");
+        let fmt = StringFormat {
+            opener: "\"",
+            closer: "\"",
+            line_end: "\\",
+            offset: 0,
+            width: 11,
+            max_width: 11,
+            trim_end: true,
+        };
+        let result = rewrite_string(&s, &fmt).unwrap();
+        for line in result.lines() {
+            assert!(!line.contains(r"\u{1f6") || line.contains(r"\u{1f600}"),
+                    "escape sequence was split across a line: {:?}", result);
+        }
+    }
+
+    #[test]
+    fn rewrite_string_round_trips() {
+        let s = r"a\nb\u{1f600}c";
+        let fmt = StringFormat {
+            opener: "\"",
+            closer: "\"",
+            line_end: "\\",
+            offset: 0,
+            width: 100,
+            max_width: 100,
+            trim_end: true,
+        };
+        let result = rewrite_string(s, &fmt).unwrap();
+        let decoded = result.trim_matches('"').replace("\\\n", "");
+        assert_eq!(decoded, s);
+    }
+}